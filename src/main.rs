@@ -1,10 +1,136 @@
 use raylib::prelude::*;
 use rand::Rng;
+use std::collections::{HashSet, VecDeque};
 use std::time::Instant;
 
-const WIDTH: i32 = 450;
-const HEIGHT: i32 = 450;
-const CELL_SIZE: i32 = 25;
+const DEFAULT_COLS: i32 = 18;
+const DEFAULT_ROWS: i32 = 18;
+const DEFAULT_CELL_SIZE: i32 = 25;
+const DEFAULT_SPEED: f32 = 10.0;
+const MAX_SPEED: f32 = 25.0;
+const SPEED_STEP: f32 = 0.5;
+const MIN_SPEED: f32 = 1.0;
+const MIN_CELL: i32 = 1;
+/// Fixed cell the snake's head spawns on; kept clear of walls in every layout.
+const SPAWN_X: i32 = 5;
+const SPAWN_Y: i32 = 5;
+/// Smallest grid dimension that keeps the `(SPAWN_X, SPAWN_Y)` spawn an
+/// interior (non-border) cell in both the open and cage layouts.
+const MIN_GRID: i32 = 7;
+const FOOD_COUNT: usize = 3;
+
+/// Built-in board layouts selectable on startup.
+#[derive(Clone, Copy, PartialEq)]
+enum Level {
+    /// An empty grid bounded only by the window edges.
+    Open,
+    /// A solid border wall with a few interior blocks.
+    Cage,
+}
+
+impl Level {
+    /// Build the set of wall cells for this layout on a `cols` x `rows` grid.
+    fn walls(self, cols: i32, rows: i32) -> HashSet<(i32, i32)> {
+        let mut walls = HashSet::new();
+        if self == Level::Cage {
+            for x in 0..cols {
+                walls.insert((x, 0));
+                walls.insert((x, rows - 1));
+            }
+            for y in 0..rows {
+                walls.insert((0, y));
+                walls.insert((cols - 1, y));
+            }
+            // A couple of interior blocks to break up the open space.
+            let mid_x = cols / 2;
+            let mid_y = rows / 2;
+            for offset in -2..=2 {
+                walls.insert((mid_x, mid_y + offset));
+                walls.insert((mid_x + offset, mid_y));
+            }
+            // Never wall off the fixed snake spawn, whatever the dimensions.
+            walls.remove(&(SPAWN_X, SPAWN_Y));
+        }
+        walls
+    }
+}
+
+/// Runtime configuration parsed from the command line, replacing the former
+/// hard-coded window and grid constants.
+struct Config {
+    cols: i32,
+    rows: i32,
+    cell: i32,
+    speed: f32,
+    level: Level,
+}
+
+impl Config {
+    fn new() -> Config {
+        Config {
+            cols: DEFAULT_COLS,
+            rows: DEFAULT_ROWS,
+            cell: DEFAULT_CELL_SIZE,
+            speed: DEFAULT_SPEED,
+            level: Level::Open,
+        }
+    }
+
+    fn width(&self) -> i32 {
+        self.cols * self.cell
+    }
+
+    fn height(&self) -> i32 {
+        self.rows * self.cell
+    }
+
+    /// Parse `--speed`, `--cols`, `--rows`, `--cell` and `--mode` (`open` or
+    /// `cage`) flags, each followed by a value. Unknown flags and unparsable
+    /// values are ignored in favour of the defaults.
+    fn from_args(args: &[String]) -> Config {
+        let mut config = Config::new();
+        let mut iter = args.iter();
+        while let Some(flag) = iter.next() {
+            match flag.as_str() {
+                "--speed" => {
+                    if let Some(value) = iter.next().and_then(|v| v.parse().ok()) {
+                        config.speed = value;
+                    }
+                }
+                "--cols" => {
+                    if let Some(value) = iter.next().and_then(|v| v.parse().ok()) {
+                        config.cols = value;
+                    }
+                }
+                "--rows" => {
+                    if let Some(value) = iter.next().and_then(|v| v.parse().ok()) {
+                        config.rows = value;
+                    }
+                }
+                "--cell" => {
+                    if let Some(value) = iter.next().and_then(|v| v.parse().ok()) {
+                        config.cell = value;
+                    }
+                }
+                "--mode" => match iter.next().map(|v| v.as_str()) {
+                    Some("cage") => config.level = Level::Cage,
+                    Some("open") => config.level = Level::Open,
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        // Reject typo'd values that would panic or spawn the snake off-grid:
+        // a zero cell size blows up `Grid::draw`'s `step_by`, and the grid must
+        // be large enough to hold the hardcoded `(5, 5)` spawn in either mode.
+        config.cell = config.cell.max(MIN_CELL);
+        config.cols = config.cols.max(MIN_GRID);
+        config.rows = config.rows.max(MIN_GRID);
+        config.speed = config.speed.max(MIN_SPEED);
+        config
+    }
+}
 
 #[derive(Clone, Copy)]
 struct Vector2 {
@@ -12,6 +138,34 @@ struct Vector2 {
     y: i32,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    fn to_vector(self) -> Vector2 {
+        match self {
+            Direction::Up => Vector2 { x: 0, y: -1 },
+            Direction::Down => Vector2 { x: 0, y: 1 },
+            Direction::Left => Vector2 { x: -1, y: 0 },
+            Direction::Right => Vector2 { x: 1, y: 0 },
+        }
+    }
+}
+
 struct Grid {
     rows: i32,
     cols: i32,
@@ -22,12 +176,14 @@ impl Grid {
         Grid { rows, cols }
     }
 
-    fn draw(&self, d: &mut RaylibDrawHandle) {
-        for x in (0..=WIDTH).step_by(CELL_SIZE as usize) {
-            d.draw_line(x, 0, x, HEIGHT, Color::LIGHTGRAY);
+    fn draw(&self, d: &mut RaylibDrawHandle, cell: i32) {
+        let width = self.cols * cell;
+        let height = self.rows * cell;
+        for x in (0..=width).step_by(cell as usize) {
+            d.draw_line(x, 0, x, height, Color::LIGHTGRAY);
         }
-        for y in (0..=HEIGHT).step_by(CELL_SIZE as usize) {
-            d.draw_line(0, y, WIDTH, y, Color::LIGHTGRAY);
+        for y in (0..=height).step_by(cell as usize) {
+            d.draw_line(0, y, width, y, Color::LIGHTGRAY);
         }
     }
 }
@@ -45,185 +201,292 @@ impl Food {
         }
     }
 
-    fn get_position(&self) -> Vector2 {
-        Vector2 {
-            x: self.position.x * CELL_SIZE,
-            y: self.position.y * CELL_SIZE,
-        }
-    }
-
-    fn draw(&self, d: &mut RaylibDrawHandle) {
+    fn draw(&self, d: &mut RaylibDrawHandle, cell: i32) {
         d.draw_rectangle(
-            self.position.x * CELL_SIZE,
-            self.position.y * CELL_SIZE,
-            CELL_SIZE,
-            CELL_SIZE,
+            self.position.x * cell,
+            self.position.y * cell,
+            cell,
+            cell,
             self.color,
         );
     }
 }
 
 struct Snake {
-    body: Vec<Vector2>,
+    body: VecDeque<Vector2>,
+    occupied: HashSet<(i32, i32)>,
     color: Color,
     grow_color: Color,
 }
 
 impl Snake {
     fn new(x: i32, y: i32, color: Color, grow_color: Color) -> Snake {
+        let mut body = VecDeque::new();
+        body.push_front(Vector2 { x, y });
+        let mut occupied = HashSet::new();
+        occupied.insert((x, y));
         Snake {
-            body: vec![Vector2 { x, y }],
+            body,
+            occupied,
             color,
             grow_color,
         }
     }
 
-    fn grow(&mut self) {
-        let last_segment = *self.body.last().unwrap();
-        self.body.push(Vector2 {
-            x: last_segment.x,
-            y: last_segment.y,
-        });
+    fn head(&self) -> Vector2 {
+        *self.body.front().unwrap()
     }
 
-    fn check_collision(&self) -> bool {
-        let head = self.body[0];
-        for segment in &self.body[1..] {
-            if head.x == segment.x && head.y == segment.y {
-                return true;
+    /// Advance the snake one cell in `move_dir`. Returns `true` if the new head
+    /// runs into an occupied cell (self-collision). When `grow` is set the tail
+    /// is kept so the snake lengthens; otherwise the tail cell is freed.
+    fn step(&mut self, move_dir: Vector2, grow: bool) -> bool {
+        let head = self.head();
+        let new_head = Vector2 {
+            x: head.x + move_dir.x,
+            y: head.y + move_dir.y,
+        };
+
+        // Free the tail before the collision test unless we are growing, so
+        // the head may legally chase into the cell the tail is vacating.
+        if !grow {
+            if let Some(tail) = self.body.pop_back() {
+                self.occupied.remove(&(tail.x, tail.y));
             }
         }
-        false
-    }
-
-    fn update(&mut self, move_dir: Vector2) {
-        let mut prev_head = self.body[0];
-        self.body[0].x += move_dir.x;
-        self.body[0].y += move_dir.y;
 
-        for segment in &mut self.body[1..] {
-            let current = *segment;
-            segment.x = prev_head.x;
-            segment.y = prev_head.y;
-            prev_head = current;
+        if self.occupied.contains(&(new_head.x, new_head.y)) {
+            return true;
         }
+
+        self.body.push_front(new_head);
+        self.occupied.insert((new_head.x, new_head.y));
+
+        false
     }
 
-    fn draw(&self, d: &mut RaylibDrawHandle) {
+    fn draw(&self, d: &mut RaylibDrawHandle, cell: i32) {
         for (i, segment) in self.body.iter().enumerate() {
             let color = if i == 0 { self.color } else { self.grow_color };
             d.draw_rectangle(
-                segment.x * CELL_SIZE,
-                segment.y * CELL_SIZE,
-                CELL_SIZE,
-                CELL_SIZE,
+                segment.x * cell,
+                segment.y * cell,
+                cell,
+                cell,
                 color,
             );
         }
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum GameState {
+    Playing,
+    GameOver,
+    Won,
+}
+
 struct Game {
+    config: Config,
     grid: Grid,
     snake: Snake,
-    food: Food,
-    move_dir: Vector2,
-    direction: String,
+    walls: HashSet<(i32, i32)>,
+    foods: Vec<Food>,
+    direction: Option<Direction>,
+    input_queue: VecDeque<Direction>,
     game_speed: f32,
     last_update: Instant,
+    state: GameState,
+    score: u32,
+    high_score: u32,
 }
 
 impl Game {
-    fn new() -> Game {
-        let grid = Grid::new((WIDTH / CELL_SIZE) as i32, (HEIGHT / CELL_SIZE) as i32);
-        let snake = Snake::new(5, 5, Color::BLUE, Color::SKYBLUE);
-        let food = Food::new(10, 15, Color::RED);
-        Game {
+    fn new(config: Config) -> Game {
+        let grid = Grid::new(config.rows, config.cols);
+        let snake = Snake::new(SPAWN_X, SPAWN_Y, Color::BLUE, Color::SKYBLUE);
+        let game_speed = config.speed;
+        let walls = config.level.walls(config.cols, config.rows);
+        let mut game = Game {
+            config,
             grid,
             snake,
-            food,
-            move_dir: Vector2 { x: 0, y: 0 },
-            direction: "none".to_string(),
-            game_speed: 10.0,
+            walls,
+            foods: Vec::new(),
+            direction: None,
+            input_queue: VecDeque::new(),
+            game_speed,
             last_update: Instant::now(),
+            state: GameState::Playing,
+            score: 0,
+            high_score: 0,
+        };
+        while game.foods.len() < FOOD_COUNT {
+            if !game.spawn_food() {
+                break;
+            }
         }
+        game
     }
 
     fn handle_keydown(&mut self, keycode: KeyboardKey) {
-        match keycode {
-            KeyboardKey::KEY_UP => {
-                if self.direction != "down" {
-                    self.move_dir = Vector2 { x: 0, y: -1 };
-                    self.direction = "up".to_string();
-                }
-            }
-            KeyboardKey::KEY_DOWN => {
-                if self.direction != "up" {
-                    self.move_dir = Vector2 { x: 0, y: 1 };
-                    self.direction = "down".to_string();
-                }
-            }
-            KeyboardKey::KEY_LEFT => {
-                if self.direction != "right" {
-                    self.move_dir = Vector2 { x: -1, y: 0 };
-                    self.direction = "left".to_string();
-                }
+        if self.state != GameState::Playing {
+            if let KeyboardKey::KEY_ENTER = keycode {
+                self.reset_snake();
             }
-            KeyboardKey::KEY_RIGHT => {
-                if self.direction != "left" {
-                    self.move_dir = Vector2 { x: 1, y: 0 };
-                    self.direction = "right".to_string();
-                }
+            return;
+        }
+        let turn = match keycode {
+            KeyboardKey::KEY_UP => Some(Direction::Up),
+            KeyboardKey::KEY_DOWN => Some(Direction::Down),
+            KeyboardKey::KEY_LEFT => Some(Direction::Left),
+            KeyboardKey::KEY_RIGHT => Some(Direction::Right),
+            _ => None,
+        };
+
+        if let Some(turn) = turn {
+            // Validate against the last queued turn (or the applied direction
+            // when the queue is empty) so a quick double-tap can't reverse.
+            let reference = self.input_queue.back().copied().or(self.direction);
+            if reference != Some(turn.opposite()) {
+                self.input_queue.push_back(turn);
             }
-            _ => {}
         }
     }
 
-    fn eat_food(&mut self) {
-        self.snake.grow();
-        self.respawn_food();
+    fn eat_food(&mut self, index: usize) {
+        self.foods.remove(index);
+        self.score += 1;
+        if self.score > self.high_score {
+            self.high_score = self.score;
+        }
+        // Ramp the difficulty up slightly with each bite, up to a ceiling.
+        if self.game_speed < MAX_SPEED {
+            self.game_speed = (self.game_speed + SPEED_STEP).min(MAX_SPEED);
+        }
+        self.spawn_food();
+    }
+
+    /// Build the set of cells that cannot hold food: every snake segment, every
+    /// currently-placed food item and every wall cell.
+    fn occupied_cells(&self) -> HashSet<(i32, i32)> {
+        let mut occupied = self.walls.clone();
+        for segment in &self.snake.body {
+            occupied.insert((segment.x, segment.y));
+        }
+        for food in &self.foods {
+            occupied.insert((food.position.x, food.position.y));
+        }
+        occupied
     }
 
-    fn respawn_food(&mut self) {
-        let max_x = (WIDTH / CELL_SIZE) as i32;
-        let max_y = (HEIGHT / CELL_SIZE) as i32;
-        self.food.position.x = rand::thread_rng().gen_range(0..max_x);
-        self.food.position.y = rand::thread_rng().gen_range(0..max_y);
+    /// Place one food item on a uniformly-chosen free cell. Returns `false`
+    /// when there are no free cells left (the board is full).
+    fn spawn_food(&mut self) -> bool {
+        let occupied = self.occupied_cells();
+        let free: Vec<(i32, i32)> = (0..self.grid.cols)
+            .flat_map(|x| (0..self.grid.rows).map(move |y| (x, y)))
+            .filter(|cell| !occupied.contains(cell))
+            .collect();
+
+        if free.is_empty() {
+            false
+        } else {
+            let (x, y) = free[rand::thread_rng().gen_range(0..free.len())];
+            self.foods.push(Food::new(x, y, Color::RED));
+            true
+        }
     }
 
     fn update(&mut self) {
-        if self.last_update.elapsed().as_secs_f32() < 1.0 / self.game_speed {
+        if self.state != GameState::Playing {
             return;
         }
 
-        self.snake.update(self.move_dir);
+        if self.last_update.elapsed().as_secs_f32() < 1.0 / self.game_speed {
+            return;
+        }
 
-        let snake_head = self.snake.body[0];
+        // Apply at most one queued turn per tick, rejecting any that would
+        // reverse the currently-applied direction.
+        if let Some(turn) = self.input_queue.pop_front() {
+            if self.direction != Some(turn.opposite()) {
+                self.direction = Some(turn);
+            }
+        }
 
-        if snake_head.x >= self.grid.cols
-            || snake_head.x < 0
-            || snake_head.y < 0
-            || snake_head.y >= self.grid.rows
-            || self.snake.check_collision()
+        let move_dir = match self.direction {
+            Some(dir) => dir.to_vector(),
+            None => {
+                self.last_update = Instant::now();
+                return;
+            }
+        };
+
+        let head = self.snake.head();
+        let new_head = Vector2 {
+            x: head.x + move_dir.x,
+            y: head.y + move_dir.y,
+        };
+
+        if new_head.x >= self.grid.cols
+            || new_head.x < 0
+            || new_head.y < 0
+            || new_head.y >= self.grid.rows
+            || self.walls.contains(&(new_head.x, new_head.y))
         {
-            self.reset_snake();
+            self.state = GameState::GameOver;
+            return;
+        }
+
+        // Decide whether the snake grows this tick before stepping, so the tail
+        // cell is only freed when we are not eating.
+        let eaten = self
+            .foods
+            .iter()
+            .position(|food| food.position.x == new_head.x && food.position.y == new_head.y);
+
+        if self.snake.step(move_dir, eaten.is_some()) {
+            self.state = GameState::GameOver;
+            return;
         }
 
-        let food_position = self.food.get_position();
-        if snake_head.x * CELL_SIZE == food_position.x && snake_head.y * CELL_SIZE == food_position.y {
-            self.eat_food();
+        if let Some(index) = eaten {
+            self.eat_food(index);
+            // No free cell left for a replacement food means the board is full
+            // and the player has won.
+            if self.foods.is_empty() {
+                self.state = GameState::Won;
+            }
         }
 
         self.last_update = Instant::now();
     }
 
     fn reset_snake(&mut self) {
-        self.snake = Snake::new(5, 5, Color::BLUE, Color::SKYBLUE);
-        self.move_dir = Vector2 { x: 0, y: 0 };
+        self.snake = Snake::new(SPAWN_X, SPAWN_Y, Color::BLUE, Color::SKYBLUE);
+        self.direction = None;
+        self.input_queue.clear();
+        self.score = 0;
+        self.game_speed = self.config.speed;
+        self.foods.clear();
+        while self.foods.len() < FOOD_COUNT {
+            if !self.spawn_food() {
+                break;
+            }
+        }
+        self.state = GameState::Playing;
+        self.last_update = Instant::now();
+    }
+
+    fn draw_walls(&self, d: &mut RaylibDrawHandle, cell: i32) {
+        for &(x, y) in &self.walls {
+            d.draw_rectangle(x * cell, y * cell, cell, cell, Color::DARKGRAY);
+        }
     }
 
     fn draw_hud(&self, d: &mut RaylibDrawHandle) {
-        let snake_head = self.snake.body[0];
+        let snake_head = self.snake.head();
         d.draw_text(
             &format!("SNAKE: {},{}", snake_head.x, snake_head.y),
             140,
@@ -238,18 +501,59 @@ impl Game {
             14,
             Color::BLUE,
         );
+        d.draw_text(
+            &format!("SCORE: {}  HIGH: {}", self.score, self.high_score),
+            10,
+            25,
+            14,
+            Color::BLUE,
+        );
+
+        let overlay = match self.state {
+            GameState::GameOver => Some((
+                format!(
+                    "GAME OVER \u{2014} Score: {} \u{2014} press ENTER to restart",
+                    self.score
+                ),
+                Color::RED,
+            )),
+            GameState::Won => Some((
+                format!(
+                    "YOU WIN! \u{2014} Score: {} \u{2014} press ENTER to restart",
+                    self.score
+                ),
+                Color::DARKGREEN,
+            )),
+            GameState::Playing => None,
+        };
+
+        if let Some((message, color)) = overlay {
+            let font_size = 14;
+            let text_width = d.measure_text(&message, font_size);
+            d.draw_text(
+                &message,
+                (self.config.width() - text_width) / 2,
+                self.config.height() / 2 - font_size / 2,
+                font_size,
+                color,
+            );
+        }
     }
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = Config::from_args(&args);
+    let cell = config.cell;
+
     let (mut rl, thread) = raylib::init()
-        .size(WIDTH, HEIGHT)
+        .size(config.width(), config.height())
         .title("Snake Game")
         .build();
 
     rl.set_target_fps(60);
 
-    let mut game = Game::new();
+    let mut game = Game::new(config);
 
     while !rl.window_should_close() {
         if let Some(key) = rl.get_key_pressed() {
@@ -261,9 +565,12 @@ fn main() {
         let mut d = rl.begin_drawing(&thread);
         d.clear_background(Color::RAYWHITE);
 
-        game.grid.draw(&mut d);
-        game.snake.draw(&mut d);
-        game.food.draw(&mut d);
+        game.grid.draw(&mut d, cell);
+        game.draw_walls(&mut d, cell);
+        game.snake.draw(&mut d, cell);
+        for food in &game.foods {
+            food.draw(&mut d, cell);
+        }
         game.draw_hud(&mut d);
     }
 }